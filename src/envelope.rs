@@ -0,0 +1,144 @@
+//! Hybrid envelope encryption: the symmetric content key is wrapped under a
+//! recipient's RSA public key with RSA-OAEP/SHA-256, so a VCF export can be
+//! shared with a specific party without exchanging a symmetric secret out of
+//! band. The records themselves are still sealed with the regular
+//! [`crate::rfc8188`] pipeline under the unwrapped content key.
+
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+use std::error::Error;
+
+/// Loads an RSA public key from either PKCS#1 or SPKI PEM, so the tool can
+/// point at a standard recipient certificate either way.
+pub fn load_public_key(pem: &str) -> Result<RsaPublicKey, Box<dyn Error>> {
+    RsaPublicKey::from_pkcs1_pem(pem)
+        .or_else(|_| RsaPublicKey::from_public_key_pem(pem))
+        .map_err(|_| "Invalid RSA public key PEM (expected PKCS#1 or SPKI)".into())
+}
+
+/// Loads an RSA private key from either PKCS#1 or PKCS#8 PEM.
+pub fn load_private_key(pem: &str) -> Result<RsaPrivateKey, Box<dyn Error>> {
+    RsaPrivateKey::from_pkcs1_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs8_pem(pem))
+        .map_err(|_| "Invalid RSA private key PEM (expected PKCS#1 or PKCS#8)".into())
+}
+
+/// Wraps `content_key` under `public_key` using RSA-OAEP/SHA-256.
+pub fn wrap_content_key(
+    public_key: &RsaPublicKey,
+    content_key: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut rng = rand::rngs::OsRng;
+    public_key
+        .encrypt(&mut rng, Oaep::new::<Sha256>(), content_key)
+        .map_err(|_| "RSA-OAEP key wrap failed".into())
+}
+
+/// Reverses [`wrap_content_key`], recovering the content key.
+pub fn unwrap_content_key(
+    private_key: &RsaPrivateKey,
+    wrapped_key: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    private_key
+        .decrypt(Oaep::new::<Sha256>(), wrapped_key)
+        .map_err(|_| "RSA-OAEP key unwrap failed".into())
+}
+
+/// Appends `wrapped_key` to `out` as a 4-byte big-endian length prefix
+/// followed by the wrapped bytes.
+pub fn write_wrapped_key(out: &mut Vec<u8>, wrapped_key: &[u8]) -> Result<(), Box<dyn Error>> {
+    let len: u32 = wrapped_key
+        .len()
+        .try_into()
+        .map_err(|_| "Wrapped key too large")?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(wrapped_key);
+    Ok(())
+}
+
+/// A wrapped key slice and the remaining stream bytes that follow it.
+type WrappedKeyAndRest<'a> = (&'a [u8], &'a [u8]);
+
+/// Reads a length-prefixed wrapped key written by [`write_wrapped_key`],
+/// returning it along with the remaining bytes.
+pub fn read_wrapped_key(bytes: &[u8]) -> Result<WrappedKeyAndRest<'_>, Box<dyn Error>> {
+    if bytes.len() < 4 {
+        return Err("Wrapped-key length prefix truncated".into());
+    }
+    let (len_bytes, rest) = bytes.split_at(4);
+    let len = u32::from_be_bytes(len_bytes.try_into()?) as usize;
+    if rest.len() < len {
+        return Err("Wrapped key truncated".into());
+    }
+    Ok(rest.split_at(len))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey};
+    use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+
+    fn test_keypair() -> (RsaPrivateKey, RsaPublicKey) {
+        let mut rng = rand::rngs::OsRng;
+        let private_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let public_key = RsaPublicKey::from(&private_key);
+        (private_key, public_key)
+    }
+
+    #[test]
+    fn wraps_and_unwraps_the_content_key() {
+        let (private_key, public_key) = test_keypair();
+        let content_key = [0x5au8; 32];
+
+        let wrapped = wrap_content_key(&public_key, &content_key).unwrap();
+        let unwrapped = unwrap_content_key(&private_key, &wrapped).unwrap();
+        assert_eq!(unwrapped, content_key);
+    }
+
+    #[test]
+    fn wrong_private_key_fails_to_unwrap() {
+        let (_, public_key) = test_keypair();
+        let (other_private_key, _) = test_keypair();
+        let wrapped = wrap_content_key(&public_key, &[0x5au8; 32]).unwrap();
+        assert!(unwrap_content_key(&other_private_key, &wrapped).is_err());
+    }
+
+    #[test]
+    fn loads_pkcs1_and_spki_public_key_pem() {
+        let (_, public_key) = test_keypair();
+
+        let pkcs1_pem = public_key.to_pkcs1_pem(LineEnding::LF).unwrap();
+        assert!(load_public_key(&pkcs1_pem).is_ok());
+
+        let spki_pem = public_key
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        assert!(load_public_key(&spki_pem).is_ok());
+    }
+
+    #[test]
+    fn loads_pkcs1_and_pkcs8_private_key_pem() {
+        let (private_key, _) = test_keypair();
+
+        let pkcs1_pem = private_key.to_pkcs1_pem(LineEnding::LF).unwrap();
+        assert!(load_private_key(&pkcs1_pem).is_ok());
+
+        let pkcs8_pem = private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        assert!(load_private_key(&pkcs8_pem).is_ok());
+    }
+
+    #[test]
+    fn length_prefixed_wrapped_key_round_trips() {
+        let mut out = Vec::new();
+        write_wrapped_key(&mut out, &[1, 2, 3, 4]).unwrap();
+        out.extend_from_slice(b"trailing stream bytes");
+
+        let (wrapped_key, rest) = read_wrapped_key(&out).unwrap();
+        assert_eq!(wrapped_key, &[1, 2, 3, 4]);
+        assert_eq!(rest, b"trailing stream bytes");
+    }
+}
@@ -0,0 +1,162 @@
+//! Passphrase-based key derivation, modeled on gocryptfs: a slow scrypt hash
+//! turns a user passphrase into a master key, and HKDF-SHA256 derives the
+//! actual per-file content key from it. The scrypt salt and cost parameters
+//! are persisted in a small plaintext header so decryption can reproduce the
+//! same master key from the passphrase alone.
+
+use hkdf::Hkdf;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use scrypt::Params;
+use sha2::Sha256;
+use std::error::Error;
+
+pub const SALT_LEN: usize = 16;
+pub const CONTENT_KEY_LEN: usize = 32;
+
+const HKDF_INFO: &[u8] = b"vcf-proof record encryption";
+
+// Ceiling applied to the scrypt cost parameters read back from a file
+// header. The header isn't authenticated, so a corrupted or adversarial
+// file could otherwise set log_n as high as scrypt's own limit of 63,
+// forcing a multi-terabyte allocation / unbounded compute on decrypt.
+// These ceilings sit comfortably above the log_n=15, r=8, p=1 this tool
+// itself writes.
+const MAX_LOG_N: u8 = 20;
+const MAX_R: u32 = 16;
+const MAX_P: u32 = 16;
+
+/// scrypt salt and cost parameters, persisted ahead of the encrypted records
+/// so a passphrase alone is enough to re-derive the master key on decrypt.
+pub struct KdfHeader {
+    pub salt: [u8; SALT_LEN],
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl KdfHeader {
+    /// Generates a fresh random salt with the given scrypt cost parameters.
+    pub fn new(log_n: u8, r: u32, p: u32) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        KdfHeader { salt, log_n, r, p }
+    }
+
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.salt);
+        out.push(self.log_n);
+        out.extend_from_slice(&self.r.to_be_bytes());
+        out.extend_from_slice(&self.p.to_be_bytes());
+    }
+
+    pub const ENCODED_LEN: usize = SALT_LEN + 1 + 4 + 4;
+
+    pub fn read_from(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < Self::ENCODED_LEN {
+            return Err("KDF header truncated".into());
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&bytes[..SALT_LEN]);
+        let log_n = bytes[SALT_LEN].min(MAX_LOG_N);
+        let r = u32::from_be_bytes(bytes[SALT_LEN + 1..SALT_LEN + 5].try_into()?).min(MAX_R);
+        let p = u32::from_be_bytes(bytes[SALT_LEN + 5..SALT_LEN + 9].try_into()?).min(MAX_P);
+        Ok(KdfHeader { salt, log_n, r, p })
+    }
+
+    fn scrypt_params(&self) -> Result<Params, Box<dyn Error>> {
+        Params::new(self.log_n, self.r, self.p, CONTENT_KEY_LEN)
+            .map_err(|_| "Invalid scrypt parameters".into())
+    }
+
+    /// Runs scrypt over `passphrase` to produce the 32-byte master key.
+    pub fn derive_master_key(&self, passphrase: &[u8]) -> Result<[u8; 32], Box<dyn Error>> {
+        let mut master_key = [0u8; CONTENT_KEY_LEN];
+        scrypt::scrypt(passphrase, &self.salt, &self.scrypt_params()?, &mut master_key)
+            .map_err(|_| "scrypt key derivation failed")?;
+        Ok(master_key)
+    }
+}
+
+/// Derives the actual per-file content key from the scrypt master key via
+/// HKDF-SHA256, so the master key itself is never used directly as an AES key.
+pub fn derive_content_key(master_key: &[u8; 32]) -> [u8; CONTENT_KEY_LEN] {
+    let hkdf = Hkdf::<Sha256>::new(None, master_key);
+    let mut content_key = [0u8; CONTENT_KEY_LEN];
+    hkdf.expand(HKDF_INFO, &mut content_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    content_key
+}
+
+/// Runs the full passphrase -> content key pipeline, generating a fresh
+/// random salt and the given scrypt cost parameters.
+pub fn derive_content_key_from_passphrase(
+    passphrase: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<(KdfHeader, [u8; CONTENT_KEY_LEN]), Box<dyn Error>> {
+    let header = KdfHeader::new(log_n, r, p);
+    let master_key = header.derive_master_key(passphrase)?;
+    Ok((header, derive_content_key(&master_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Minimal scrypt cost parameters so the test suite stays fast; production
+    // uses SCRYPT_LOG_N = 15 in main.rs.
+    const TEST_LOG_N: u8 = 4;
+
+    #[test]
+    fn header_round_trips_through_bytes() {
+        let header = KdfHeader::new(TEST_LOG_N, 8, 1);
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes);
+
+        let parsed = KdfHeader::read_from(&bytes).unwrap();
+        assert_eq!(parsed.salt, header.salt);
+        assert_eq!(parsed.log_n, header.log_n);
+        assert_eq!(parsed.r, header.r);
+        assert_eq!(parsed.p, header.p);
+    }
+
+    #[test]
+    fn same_passphrase_and_header_reproduce_the_same_content_key() {
+        let (header, content_key) =
+            derive_content_key_from_passphrase(b"correct horse battery staple", TEST_LOG_N, 8, 1)
+                .unwrap();
+
+        let master_key = header.derive_master_key(b"correct horse battery staple").unwrap();
+        assert_eq!(derive_content_key(&master_key), content_key);
+    }
+
+    #[test]
+    fn wrong_passphrase_derives_a_different_content_key() {
+        let (header, content_key) =
+            derive_content_key_from_passphrase(b"correct horse battery staple", TEST_LOG_N, 8, 1)
+                .unwrap();
+
+        let master_key = header.derive_master_key(b"wrong passphrase").unwrap();
+        assert_ne!(derive_content_key(&master_key), content_key);
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        assert!(KdfHeader::read_from(&[0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn clamps_oversized_cost_parameters_read_from_a_header() {
+        let mut bytes = vec![0u8; KdfHeader::ENCODED_LEN];
+        bytes[SALT_LEN] = 255;
+        bytes[SALT_LEN + 1..SALT_LEN + 5].copy_from_slice(&u32::MAX.to_be_bytes());
+        bytes[SALT_LEN + 5..SALT_LEN + 9].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let header = KdfHeader::read_from(&bytes).unwrap();
+        assert_eq!(header.log_n, MAX_LOG_N);
+        assert_eq!(header.r, MAX_R);
+        assert_eq!(header.p, MAX_P);
+    }
+}
@@ -1,52 +1,124 @@
-use aes::{
-    Aes256,
-    cipher::{Block, BlockEncrypt, KeyInit, generic_array::GenericArray},
-};
+mod envelope;
+mod kdf;
+mod rfc8188;
+mod shamir;
+mod vcf_serialize;
+
 use flate2::read::MultiGzDecoder;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::env;
 use std::fs::File;
 use std::io::{BufReader, Write};
-use vcf::{VCFError, VCFReader, VCFRecord};
-
-fn encrypt_vcf_data(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let cipher = Aes256::new_from_slice(key).map_err(|_| "Invalid key length")?;
-
-    // Pad data to multiple of 16 bytes (AES block size)
-    let block_size = 16;
-    let padded_len = ((data.len() + block_size - 1) / block_size) * block_size;
-    let mut padded_data = vec![0u8; padded_len];
-    padded_data[..data.len()].copy_from_slice(data);
-
-    // Encrypt each 16-byte block
-    let mut encrypted = Vec::new();
-    for chunk in padded_data.chunks(block_size) {
-        let mut block = [0u8; 16];
-        block.copy_from_slice(chunk);
-        cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
-        encrypted.extend_from_slice(&block);
+use vcf::VCFReader;
+
+// scrypt cost parameters: N = 2^15, r = 8, p = 1 (gocryptfs' default "logN=15").
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+// RFC 8188 record size: plaintext chunk plus its tag and delimiter fits in
+// one 4 KiB record.
+const RFC8188_RECORD_SIZE: u32 = 4096;
+
+const CONTENT_KEY_LEN: usize = 32;
+
+const INPUT_VCF_PATH: &str =
+    "./data/ALL.chr22.shapeit2_integrated_snvindels_v2a_27022019.GRCh38.phased.vcf.gz";
+const OUTPUT_PATH: &str = "encrypted_vcf_records.bin";
+const DECRYPTED_OUTPUT_PATH: &str = "decrypted_vcf_records.txt";
+
+/// Leading byte of the output file identifying how the content key is
+/// protected, so decryption knows which header shape follows.
+const MODE_PASSPHRASE: u8 = 1;
+const MODE_ENVELOPE: u8 = 2;
+const MODE_SHAMIR: u8 = 3;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    match env::var("VCF_PROOF_ACTION").as_deref() {
+        Ok("decrypt") => run_decrypt(),
+        _ => run_encrypt(),
     }
+}
+
+/// Protects a freshly generated content key for the configured mode, writing
+/// `mode-byte || key-header` to `out` and returning the content key.
+/// - `VCF_PROOF_RECIPIENT_PUBKEY`: wrap the key under a recipient's RSA key.
+/// - `VCF_PROOF_SHAMIR_K`/`VCF_PROOF_SHAMIR_N`: split the key into shares,
+///   written as `share-<x>.bin` under `VCF_PROOF_SHARE_DIR` (default `.`),
+///   never into the output file itself.
+/// - otherwise: derive the key from `VCF_PROOF_PASSPHRASE` via scrypt+HKDF.
+fn protect_content_key(out: &mut Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if let Ok(recipient_pubkey_path) = env::var("VCF_PROOF_RECIPIENT_PUBKEY") {
+        let public_key_pem = std::fs::read_to_string(&recipient_pubkey_path)?;
+        let public_key = envelope::load_public_key(&public_key_pem)?;
+
+        let mut content_key = [0u8; CONTENT_KEY_LEN];
+        OsRng.fill_bytes(&mut content_key);
+        let wrapped_key = envelope::wrap_content_key(&public_key, &content_key)?;
+
+        out.push(MODE_ENVELOPE);
+        envelope::write_wrapped_key(out, &wrapped_key)?;
+        Ok(content_key.to_vec())
+    } else if let (Ok(k), Ok(n)) = (
+        env::var("VCF_PROOF_SHAMIR_K"),
+        env::var("VCF_PROOF_SHAMIR_N"),
+    ) {
+        let k: u8 = k.parse().map_err(|_| "VCF_PROOF_SHAMIR_K must be a u8")?;
+        let n: u8 = n.parse().map_err(|_| "VCF_PROOF_SHAMIR_N must be a u8")?;
+
+        let mut content_key = [0u8; CONTENT_KEY_LEN];
+        OsRng.fill_bytes(&mut content_key);
+        let shares = shamir::split_key(&content_key, k, n)?;
+
+        let share_dir = env::var("VCF_PROOF_SHARE_DIR").unwrap_or_else(|_| ".".to_string());
+        for share in &shares {
+            let x = share[0];
+            let mut share_file = File::create(format!("{share_dir}/share-{x}.bin"))?;
+            share_file.write_all(share)?;
+        }
+
+        out.push(MODE_SHAMIR);
+        out.push(k);
+        out.push(n);
+        Ok(content_key.to_vec())
+    } else {
+        let passphrase = env::var("VCF_PROOF_PASSPHRASE").map_err(|_| {
+            "Set VCF_PROOF_PASSPHRASE, VCF_PROOF_RECIPIENT_PUBKEY, or VCF_PROOF_SHAMIR_K/N"
+        })?;
+        let (kdf_header, content_key) = kdf::derive_content_key_from_passphrase(
+            passphrase.as_bytes(),
+            SCRYPT_LOG_N,
+            SCRYPT_R,
+            SCRYPT_P,
+        )?;
 
-    Ok(encrypted)
+        out.push(MODE_PASSPHRASE);
+        kdf_header.write_to(out);
+        Ok(content_key.to_vec())
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 32-byte key for AES-256
-    let key = hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
-        .map_err(|_| "Invalid key format")?;
+fn run_encrypt() -> Result<(), Box<dyn std::error::Error>> {
+    let mut key_header = Vec::new();
+    let key = protect_content_key(&mut key_header)?;
 
     // Read VCF file
     let mut reader = VCFReader::new(BufReader::new(MultiGzDecoder::new(File::open(
-        "./data/ALL.chr22.shapeit2_integrated_snvindels_v2a_27022019.GRCh38.phased.vcf.gz",
+        INPUT_VCF_PATH,
     )?)))?;
 
-    let mut encrypted_records = Vec::new();
+    let mut plaintext = Vec::new();
     let mut vcf_record = reader.empty_record();
 
-    // Read and encrypt each record
+    // Read every record and append it to a single byte stream, rather than
+    // encrypting and buffering each record's ciphertext individually.
     let mut record_count = 0;
     while reader.next_record(&mut vcf_record)? {
-        let record_data = format!("{:?}", vcf_record).into_bytes();
-        let encrypted = encrypt_vcf_data(&key, &record_data)?;
-        encrypted_records.push(encrypted);
+        let record_line = vcf_serialize::serialize_record(&vcf_record);
+        vcf_serialize::validate_round_trip(&record_line)?;
+        plaintext.extend_from_slice(record_line.as_bytes());
+        plaintext.push(b'\n');
 
         record_count += 1;
         if record_count % 100 == 0 {
@@ -55,11 +127,82 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     println!("Finished processing a total of {} records", record_count);
 
-    // Save encrypted records to a file
-    let mut output_file = File::create("encrypted_vcf_records.bin")?;
-    for encrypted in encrypted_records {
-        output_file.write_all(&encrypted)?;
+    // Seal the whole stream as an RFC 8188 aes128gcm container: a fresh
+    // random salt keys the record stream, so the key header (re-deriving or
+    // recovering the content key) plus this salt is all decryption needs.
+    let mut rfc8188_salt = [0u8; rfc8188::SALT_LEN];
+    OsRng.fill_bytes(&mut rfc8188_salt);
+    let sealed_stream = rfc8188::encrypt_stream(
+        &key,
+        rfc8188_salt,
+        RFC8188_RECORD_SIZE,
+        Vec::new(),
+        &plaintext,
+    )?;
+
+    // Save the key header (passphrase KDF params, an RSA-wrapped content
+    // key, or the Shamir threshold) followed by the sealed RFC 8188 stream.
+    let mut output_file = File::create(OUTPUT_PATH)?;
+    output_file.write_all(&key_header)?;
+    output_file.write_all(&sealed_stream)?;
+
+    Ok(())
+}
+
+/// Reverses [`run_encrypt`]: recovers the content key for whichever mode the
+/// file's leading byte selects, decrypts the RFC 8188 stream, and validates
+/// every reassembled record line before writing it out. Run with
+/// `VCF_PROOF_ACTION=decrypt`.
+fn run_decrypt() -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(OUTPUT_PATH)?;
+    let (&mode, body) = data.split_first().ok_or("Empty input file")?;
+
+    let (key, sealed_stream) = match mode {
+        MODE_PASSPHRASE => {
+            if body.len() < kdf::KdfHeader::ENCODED_LEN {
+                return Err("Truncated passphrase header".into());
+            }
+            let (header_bytes, rest) = body.split_at(kdf::KdfHeader::ENCODED_LEN);
+            let kdf_header = kdf::KdfHeader::read_from(header_bytes)?;
+            let passphrase = env::var("VCF_PROOF_PASSPHRASE")
+                .map_err(|_| "Set VCF_PROOF_PASSPHRASE to decrypt this file")?;
+            let master_key = kdf_header.derive_master_key(passphrase.as_bytes())?;
+            (kdf::derive_content_key(&master_key).to_vec(), rest)
+        }
+        MODE_ENVELOPE => {
+            let (wrapped_key, rest) = envelope::read_wrapped_key(body)?;
+            let private_key_path = env::var("VCF_PROOF_RECIPIENT_PRIVKEY")
+                .map_err(|_| "Set VCF_PROOF_RECIPIENT_PRIVKEY to decrypt this file")?;
+            let private_key_pem = std::fs::read_to_string(&private_key_path)?;
+            let private_key = envelope::load_private_key(&private_key_pem)?;
+            (envelope::unwrap_content_key(&private_key, wrapped_key)?, rest)
+        }
+        MODE_SHAMIR => {
+            let (&k, rest) = body.split_first().ok_or("Truncated Shamir header")?;
+            let (&_n, rest) = rest.split_first().ok_or("Truncated Shamir header")?;
+            let share_paths = env::var("VCF_PROOF_SHARE_FILES").map_err(|_| {
+                "Set VCF_PROOF_SHARE_FILES to a comma-separated list of share files"
+            })?;
+            let shares: Vec<Vec<u8>> = share_paths
+                .split(',')
+                .map(std::fs::read)
+                .collect::<Result<_, _>>()?;
+            (shamir::recover_and_verify_key(&shares, k, rest)?, rest)
+        }
+        other => return Err(format!("Unknown key-protection mode byte: {other}").into()),
+    };
+
+    let plaintext = rfc8188::decrypt_stream(&key, sealed_stream)?;
+    let plaintext = String::from_utf8(plaintext)?;
+
+    let mut output_file = File::create(DECRYPTED_OUTPUT_PATH)?;
+    let mut record_count = 0;
+    for line in plaintext.lines() {
+        vcf_serialize::validate_round_trip(line)?;
+        writeln!(output_file, "{line}")?;
+        record_count += 1;
     }
+    println!("Decrypted a total of {} records", record_count);
 
     Ok(())
 }
@@ -0,0 +1,240 @@
+//! K-of-N Shamir secret sharing over GF(256), used to split the content key
+//! across multiple custodians (e.g. a patient plus an institution) so that no
+//! single party can decrypt a VCF export alone.
+//!
+//! Each key byte is shared independently: a degree-(K-1) polynomial with that
+//! byte as its constant term is evaluated at N distinct non-zero
+//! x-coordinates (1..=N), and the secret is recovered from any K of them via
+//! Lagrange interpolation at x = 0.
+
+use crate::rfc8188;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::error::Error;
+
+const MIN_SHARE_LEN: usize = 16;
+
+/// GF(256) multiplication under the AES reduction polynomial x^8+x^4+x^3+x+1
+/// (0x11b).
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0u8;
+    for _ in 0..8 {
+        if b & 1 != 0 {
+            result ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    result
+}
+
+fn gf_pow(a: u8, mut exp: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    while exp > 0 {
+        if exp & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// GF(256) multiplicative inverse, via Fermat's little theorem (a^254 = a^-1).
+fn gf_inv(a: u8) -> u8 {
+    gf_pow(a, 254)
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    gf_mul(a, gf_inv(b))
+}
+
+/// Evaluates the polynomial with the given coefficients (constant term
+/// first) at `x` over GF(256), using Horner's method.
+fn eval_polynomial(coefficients: &[u8], x: u8) -> u8 {
+    let mut result = 0u8;
+    for &coefficient in coefficients.iter().rev() {
+        result = gf_mul(result, x) ^ coefficient;
+    }
+    result
+}
+
+/// Splits `key` into `n` shares such that any `k` of them reconstruct it.
+/// Each share is encoded as `x-coordinate || share-bytes`.
+pub fn split_key(key: &[u8], k: u8, n: u8) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    if k == 0 || n == 0 || k > n {
+        return Err("Need 1 <= k <= n".into());
+    }
+    if n as usize > 255 {
+        return Err("n must be at most 255 (non-zero GF(256) x-coordinates)".into());
+    }
+
+    // coefficients[byte_index] = [secret_byte, c1, c2, ..., c_{k-1}] with
+    // random non-secret coefficients, one polynomial per key byte.
+    let mut coefficients = vec![vec![0u8; k as usize]; key.len()];
+    for (byte_index, secret_byte) in key.iter().enumerate() {
+        coefficients[byte_index][0] = *secret_byte;
+        if k > 1 {
+            OsRng.fill_bytes(&mut coefficients[byte_index][1..]);
+        }
+    }
+
+    let mut shares = Vec::with_capacity(n as usize);
+    for x in 1..=n {
+        let mut share = Vec::with_capacity(1 + key.len());
+        share.push(x);
+        for byte_coefficients in &coefficients {
+            share.push(eval_polynomial(byte_coefficients, x));
+        }
+        shares.push(share);
+    }
+    Ok(shares)
+}
+
+/// Reconstructs the original key from `k` shares via Lagrange interpolation
+/// at x = 0. `threshold` is the `k` the shares were split with (as low as 1);
+/// fewer shares than that can't determine the polynomial. Rejects duplicate
+/// x-coordinates and implausibly short shares.
+pub fn recover_key(shares: &[Vec<u8>], threshold: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+    if threshold == 0 {
+        return Err("Threshold must be at least 1".into());
+    }
+    if shares.len() < threshold as usize {
+        return Err("Fewer shares were provided than the threshold requires".into());
+    }
+    for share in shares {
+        if share.len() < MIN_SHARE_LEN {
+            return Err("Share is shorter than the minimum of 16 bytes".into());
+        }
+    }
+    let share_len = shares[0].len();
+    if shares.iter().any(|s| s.len() != share_len) {
+        return Err("Shares have inconsistent lengths".into());
+    }
+
+    let xs: Vec<u8> = shares.iter().map(|s| s[0]).collect();
+    if xs.contains(&0) {
+        return Err("Share x-coordinate must be non-zero".into());
+    }
+    for i in 0..xs.len() {
+        for j in i + 1..xs.len() {
+            if xs[i] == xs[j] {
+                return Err("Duplicate share x-coordinate".into());
+            }
+        }
+    }
+
+    let secret_len = share_len - 1;
+    let mut secret = vec![0u8; secret_len];
+    for byte_index in 0..secret_len {
+        let mut acc = 0u8;
+        for (i, share) in shares.iter().enumerate() {
+            let xi = xs[i];
+            let yi = share[1 + byte_index];
+
+            // Lagrange basis polynomial l_i(0) = prod_{j != i} (0 - x_j) / (x_i - x_j),
+            // and subtraction is XOR in GF(256) so (0 - x_j) == x_j.
+            let mut numerator = 1u8;
+            let mut denominator = 1u8;
+            for (j, &xj) in xs.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                numerator = gf_mul(numerator, xj);
+                denominator = gf_mul(denominator, xi ^ xj);
+            }
+            acc ^= gf_mul(yi, gf_div(numerator, denominator));
+        }
+        secret[byte_index] = acc;
+    }
+    Ok(secret)
+}
+
+/// Reconstructs the content key from `shares` (split with the given
+/// `threshold`) and verifies it by decrypting `sealed_stream` (the file's
+/// RFC 8188 container) with it, rejecting a reconstruction that recovered
+/// the wrong key.
+pub fn recover_and_verify_key(
+    shares: &[Vec<u8>],
+    threshold: u8,
+    sealed_stream: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key = recover_key(shares, threshold)?;
+    rfc8188::decrypt_stream(&key, sealed_stream)
+        .map_err(|_| "Recovered key failed to decrypt the RFC 8188 stream")?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [0x7a; 32];
+
+    #[test]
+    fn reconstructs_the_key_from_a_threshold_of_shares() {
+        let shares = split_key(&KEY, 3, 5).unwrap();
+        let recovered = recover_key(&shares[1..4], 3).unwrap();
+        assert_eq!(recovered, KEY);
+    }
+
+    #[test]
+    fn any_k_of_n_shares_reconstruct_the_same_key() {
+        let shares = split_key(&KEY, 3, 5).unwrap();
+        let recovered_a =
+            recover_key(&[shares[0].clone(), shares[1].clone(), shares[2].clone()], 3).unwrap();
+        let recovered_b =
+            recover_key(&[shares[2].clone(), shares[3].clone(), shares[4].clone()], 3).unwrap();
+        assert_eq!(recovered_a, KEY);
+        assert_eq!(recovered_b, KEY);
+    }
+
+    #[test]
+    fn reconstructs_the_key_from_a_single_share_when_split_with_k_one() {
+        let shares = split_key(&KEY, 1, 3).unwrap();
+        let recovered = recover_key(&shares[1..2], 1).unwrap();
+        assert_eq!(recovered, KEY);
+    }
+
+    #[test]
+    fn rejects_fewer_shares_than_the_threshold() {
+        let shares = split_key(&KEY, 3, 5).unwrap();
+        assert!(recover_key(&shares[..2], 3).is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_x_coordinates() {
+        let shares = split_key(&KEY, 2, 5).unwrap();
+        let duplicated = vec![shares[0].clone(), shares[0].clone()];
+        assert!(recover_key(&duplicated, 2).is_err());
+    }
+
+    #[test]
+    fn rejects_shares_shorter_than_the_minimum() {
+        let too_short = vec![vec![1u8; 10], vec![2u8; 10]];
+        assert!(recover_key(&too_short, 2).is_err());
+    }
+
+    #[test]
+    fn recover_and_verify_accepts_a_correct_reconstruction() {
+        let shares = split_key(&KEY, 2, 3).unwrap();
+        let sealed = rfc8188::encrypt_stream(&KEY, [0x55; rfc8188::SALT_LEN], 4096, Vec::new(), b"a vcf record")
+            .unwrap();
+        let recovered = recover_and_verify_key(&shares[..2], 2, &sealed).unwrap();
+        assert_eq!(recovered, KEY);
+    }
+
+    #[test]
+    fn recover_and_verify_rejects_shares_for_the_wrong_key() {
+        let wrong_key = [0x01; 32];
+        let shares = split_key(&wrong_key, 2, 3).unwrap();
+        let sealed = rfc8188::encrypt_stream(&KEY, [0x55; rfc8188::SALT_LEN], 4096, Vec::new(), b"a vcf record")
+            .unwrap();
+        assert!(recover_and_verify_key(&shares[..2], 2, &sealed).is_err());
+    }
+}